@@ -0,0 +1,225 @@
+/*
+ * paperback: paper backup generator suitable for long-term storage
+ * Copyright (C) 2018-2020 Aleksa Sarai <cyphar@cyphar.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use crate::v0::{Error, KeyShard, KeyShardBuilder, MainDocument, MainDocumentBuilder};
+
+use bip39::{Mnemonic, Seed};
+use ed25519_dalek::{Keypair, PublicKey, SecretKey};
+use hmac::{Hmac, Mac, NewMac};
+use sha2::Sha512;
+
+type HmacSha512 = Hmac<Sha512>;
+
+const ED25519_SEED_HMAC_KEY: &[u8] = b"ed25519 seed";
+
+// Only hardened derivation is defined for Ed25519 (SLIP-0010), so every
+// path element is implicitly treated as hardened.
+const HARDENED_OFFSET: u32 = 1 << 31;
+
+// One step of a SLIP-0010 Ed25519 extended key: a 32-byte key plus the
+// 32-byte chain code used to derive the next child.
+struct ExtendedIdentityKey {
+    key: [u8; 32],
+    chain_code: [u8; 32],
+}
+
+impl ExtendedIdentityKey {
+    fn from_hmac(mac_key: &[u8], body: impl FnOnce(&mut HmacSha512)) -> Result<Self, Error> {
+        let mut mac =
+            HmacSha512::new_from_slice(mac_key).map_err(|err| Error::Other(err.to_string()))?;
+        body(&mut mac);
+        let result = mac.finalize().into_bytes();
+
+        let mut key = [0u8; 32];
+        let mut chain_code = [0u8; 32];
+        key.copy_from_slice(&result[..32]);
+        chain_code.copy_from_slice(&result[32..]);
+
+        Ok(Self { key, chain_code })
+    }
+
+    fn master(seed: &[u8]) -> Result<Self, Error> {
+        Self::from_hmac(ED25519_SEED_HMAC_KEY, |mac| mac.update(seed))
+    }
+
+    fn derive_hardened_child(&self, index: u32) -> Result<Self, Error> {
+        let index = index | HARDENED_OFFSET;
+        Self::from_hmac(&self.chain_code, |mac| {
+            mac.update(&[0x00]);
+            mac.update(&self.key);
+            mac.update(&index.to_be_bytes());
+        })
+    }
+
+    fn into_keypair(self) -> Result<Keypair, Error> {
+        let secret =
+            SecretKey::from_bytes(&self.key).map_err(|err| Error::Other(err.to_string()))?;
+        let public = PublicKey::from(&secret);
+
+        Ok(Keypair { secret, public })
+    }
+}
+
+/// Derives the Ed25519 identity keypair used to sign a document or shard
+/// from a BIP-39 master mnemonic and a hardened derivation path, following
+/// the SLIP-0010 scheme for Ed25519. Deriving from the same mnemonic and
+/// path always yields the same keypair, which is what lets a backup's
+/// identity be reproduced later without ever having stored the private key
+/// -- only the mnemonic and the path are needed. [`Backup`] uses a
+/// single-element path and records that index as the document's
+/// `identity_derivation_index`; longer paths are not recorded anywhere.
+pub fn derive_identity_keypair(master_mnemonic: &Mnemonic, path: &[u32]) -> Result<Keypair, Error> {
+    let seed = Seed::new(master_mnemonic, "");
+
+    let mut extended = ExtendedIdentityKey::master(seed.as_bytes())?;
+    for &index in path {
+        extended = extended.derive_hardened_child(index)?;
+    }
+
+    extended.into_keypair()
+}
+
+/// Owns the identity keypair shared by a single backup -- the main document
+/// and every shard handed out for it -- derived deterministically from a
+/// master mnemonic and a hardened SLIP-0010 index instead of a keypair that
+/// would need to be backed up on its own. Signing both the document and its
+/// shards through the same `Backup` guarantees they share one identity,
+/// exactly as they would if the keypair had been generated and held in
+/// memory for the lifetime of the dealing process.
+pub struct Backup {
+    id_keypair: Keypair,
+    index: u32,
+}
+
+impl Backup {
+    pub fn new(master_mnemonic: &Mnemonic, index: u32) -> Result<Self, Error> {
+        let id_keypair = derive_identity_keypair(master_mnemonic, &[index])?;
+        Ok(Self { id_keypair, index })
+    }
+
+    /// Signs an unsigned [`MainDocumentBuilder`] with this backup's derived
+    /// identity, recording `index` as the document's
+    /// `identity_derivation_index` so the same keypair can always be
+    /// re-derived later from the mnemonic alone.
+    pub fn sign_document(&self, mut doc: MainDocumentBuilder) -> MainDocument {
+        doc.meta.identity_derivation_index = Some(self.index);
+        doc.sign(&self.id_keypair)
+    }
+
+    /// Signs an unsigned [`KeyShardBuilder`] with the same derived identity
+    /// used for [`Backup::sign_document`], so every shard handed out for
+    /// this backup verifies against the same identity as the document it
+    /// protects.
+    pub fn sign_shard(&self, shard: KeyShardBuilder) -> KeyShard {
+        shard.sign(&self.id_keypair)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::v0::{
+        DocumentNonce, MainDocumentMeta, Shard, XChaChaPolyNonce, DEFAULT_CHECKSUM_ALGORITHM,
+    };
+    use ed25519_dalek::Verifier;
+    use multihash::MultihashDigest;
+    use quickcheck::{Arbitrary, Gen};
+
+    fn test_mnemonic() -> Mnemonic {
+        Mnemonic::from_entropy(&[0u8; 32], bip39::Language::English).unwrap()
+    }
+
+    fn test_document_builder() -> MainDocumentBuilder {
+        MainDocumentBuilder {
+            meta: MainDocumentMeta {
+                version: 0,
+                quorum_size: 2,
+                identity_derivation_index: None,
+            },
+            nonce: DocumentNonce::XChaCha20Poly1305(XChaChaPolyNonce::default()),
+            ciphertext: vec![1, 2, 3],
+        }
+    }
+
+    fn test_shard_builder() -> KeyShardBuilder {
+        let mut gen = Gen::new(16);
+        KeyShardBuilder {
+            version: 0,
+            doc_chksum: DEFAULT_CHECKSUM_ALGORITHM.digest(b"a main document"),
+            shard: Shard::arbitrary(&mut gen),
+        }
+    }
+
+    #[test]
+    fn derivation_is_deterministic() {
+        let mnemonic = test_mnemonic();
+
+        let keypair1 = derive_identity_keypair(&mnemonic, &[0, 1]).unwrap();
+        let keypair2 = derive_identity_keypair(&mnemonic, &[0, 1]).unwrap();
+
+        assert_eq!(keypair1.public, keypair2.public);
+    }
+
+    #[test]
+    fn different_paths_give_different_keys() {
+        let mnemonic = test_mnemonic();
+
+        let keypair1 = derive_identity_keypair(&mnemonic, &[0]).unwrap();
+        let keypair2 = derive_identity_keypair(&mnemonic, &[1]).unwrap();
+
+        assert_ne!(keypair1.public, keypair2.public);
+    }
+
+    #[test]
+    fn derived_identity_is_recorded_and_verifiable() {
+        let mnemonic = test_mnemonic();
+        let backup = Backup::new(&mnemonic, 0).unwrap();
+
+        let doc = backup.sign_document(test_document_builder());
+
+        assert_eq!(doc.inner.meta.identity_derivation_index, Some(0));
+
+        let expected_keypair = derive_identity_keypair(&mnemonic, &[0]).unwrap();
+        assert_eq!(doc.identity.id_public_key, expected_keypair.public);
+
+        let signable_bytes = doc.inner.signable_bytes(&doc.identity.id_public_key);
+        assert!(doc
+            .identity
+            .id_public_key
+            .verify(&signable_bytes, &doc.identity.id_signature)
+            .is_ok());
+    }
+
+    #[test]
+    fn document_and_shard_share_derived_identity() {
+        let mnemonic = test_mnemonic();
+        let backup = Backup::new(&mnemonic, 0).unwrap();
+
+        let doc = backup.sign_document(test_document_builder());
+        let shard = backup.sign_shard(test_shard_builder());
+
+        assert_eq!(doc.identity.id_public_key, shard.identity.id_public_key);
+
+        let signable_bytes = shard.inner.signable_bytes(&shard.identity.id_public_key);
+        assert!(shard
+            .identity
+            .id_public_key
+            .verify(&signable_bytes, &shard.identity.id_signature)
+            .is_ok());
+    }
+}
@@ -22,9 +22,10 @@ use crate::{
 };
 
 use aead::{generic_array::GenericArray, Aead, AeadCore, NewAead};
+use argon2::{Algorithm, Argon2, ParamsBuilder, Version};
 use bip39::{Language, Mnemonic};
-use chacha20poly1305::ChaCha20Poly1305;
-use ed25519_dalek::{Keypair, PublicKey, Signature, Signer};
+use chacha20poly1305::{ChaCha20Poly1305, XChaCha20Poly1305};
+use ed25519_dalek::{Keypair, PublicKey, Signature, Signer, Verifier};
 use multihash::{Code, Multihash, MultihashDigest};
 use rand::RngCore;
 use unsigned_varint::encode as varuint_encode;
@@ -38,6 +39,9 @@ const CHACHAPOLY_KEY_LENGTH: usize = 32usize;
 type ChaChaPolyNonce = GenericArray<u8, <ChaCha20Poly1305 as AeadCore>::NonceSize>;
 const CHACHAPOLY_NONCE_LENGTH: usize = 12usize;
 
+type XChaChaPolyNonce = GenericArray<u8, <XChaCha20Poly1305 as AeadCore>::NonceSize>;
+const XCHACHAPOLY_NONCE_LENGTH: usize = 24usize;
+
 #[cfg(test)]
 #[test]
 fn check_length_consts() {
@@ -45,14 +49,157 @@ fn check_length_consts() {
     // in a test...
     assert_eq!(CHACHAPOLY_KEY_LENGTH, ChaChaPolyKey::default().len());
     assert_eq!(CHACHAPOLY_NONCE_LENGTH, ChaChaPolyNonce::default().len());
+    assert_eq!(XCHACHAPOLY_NONCE_LENGTH, XChaChaPolyNonce::default().len());
+}
+
+// A random 96-bit ChaCha20-Poly1305 nonce has a non-negligible birthday
+// collision probability once many shards/documents are minted from related
+// key material, and nonce reuse under ChaCha20-Poly1305 is catastrophic. New
+// shards and documents are always sealed with the wider, collision-safe
+// XChaCha20-Poly1305 nonce; the narrower ChaCha20-Poly1305 variant is kept
+// around purely so older v0 documents/shards can still be decrypted.
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum DocumentNonce {
+    ChaCha20Poly1305(ChaChaPolyNonce),
+    XChaCha20Poly1305(XChaChaPolyNonce),
+}
+
+const ARGON2_SALT_LENGTH: usize = 16usize;
+type Argon2Salt = [u8; ARGON2_SALT_LENGTH];
+
+// Sane-but-conservative defaults for interactive use -- 64 MiB of memory,
+// 3 iterations, a single lane. Callers can't currently override these, but
+// the parameters travel with the shard so a future default change doesn't
+// break existing passphrase-encrypted shards.
+const ARGON2_DEFAULT_MEMORY_COST_KIB: u32 = 64 * 1024;
+const ARGON2_DEFAULT_TIME_COST: u32 = 3;
+const ARGON2_DEFAULT_PARALLELISM: u32 = 1;
+
+// Upper bounds on the Argon2 parameters accepted from an untrusted shard.
+// `EncryptedKeyShard`'s whole threat model is hostile/corrupted shards (see
+// the quorum validation in `recover.rs`), so a shard can't be allowed to
+// make the recovering process allocate an unreasonable amount of memory or
+// spin for an unreasonable amount of time just by claiming an absurd
+// memory_cost_kib/time_cost/parallelism -- that's a DoS triggered by data
+// the caller doesn't control. Chosen generously above the interactive
+// defaults above, not as a recommended ceiling for real use.
+const ARGON2_MAX_MEMORY_COST_KIB: u32 = 1024 * 1024; // 1 GiB
+const ARGON2_MAX_TIME_COST: u32 = 16;
+const ARGON2_MAX_PARALLELISM: u32 = 16;
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct Argon2Params {
+    memory_cost_kib: u32,
+    time_cost: u32,
+    parallelism: u32,
+}
+
+impl Argon2Params {
+    fn is_allowed(&self) -> bool {
+        (1..=ARGON2_MAX_MEMORY_COST_KIB).contains(&self.memory_cost_kib)
+            && (1..=ARGON2_MAX_TIME_COST).contains(&self.time_cost)
+            && (1..=ARGON2_MAX_PARALLELISM).contains(&self.parallelism)
+    }
+}
+
+impl Default for Argon2Params {
+    fn default() -> Self {
+        Self {
+            memory_cost_kib: ARGON2_DEFAULT_MEMORY_COST_KIB,
+            time_cost: ARGON2_DEFAULT_TIME_COST,
+            parallelism: ARGON2_DEFAULT_PARALLELISM,
+        }
+    }
+}
+
+#[cfg(test)]
+impl quickcheck::Arbitrary for Argon2Params {
+    fn arbitrary(_g: &mut quickcheck::Gen) -> Self {
+        // Running Argon2id with arbitrary memory/iteration counts in a
+        // property test would make the test suite glacially slow (or
+        // memory-hungry), so we only ever exercise the real defaults here.
+        Self::default()
+    }
+}
+
+fn derive_argon2_key(
+    passphrase: &str,
+    salt: &Argon2Salt,
+    params: &Argon2Params,
+) -> Result<ChaChaPolyKey, Error> {
+    // `params` may have come straight off the wire of an untrusted shard --
+    // fail closed rather than handing an attacker-chosen memory/time cost to
+    // Argon2.
+    if !params.is_allowed() {
+        return Err(Error::InvariantViolation(
+            "argon2 parameters exceed the allowed range".to_string(),
+        ));
+    }
+
+    let argon2_params = ParamsBuilder::new()
+        .m_cost(params.memory_cost_kib)
+        .t_cost(params.time_cost)
+        .p_cost(params.parallelism)
+        .output_len(CHACHAPOLY_KEY_LENGTH)
+        .build()
+        .map_err(|err| Error::Other(err.to_string()))?;
+    let argon2 = Argon2::new(Algorithm::Argon2id, Version::V0x13, argon2_params);
+
+    let mut shard_key = ChaChaPolyKey::default();
+    argon2
+        .hash_password_into(passphrase.as_bytes(), salt, &mut shard_key)
+        .map_err(|err| Error::Other(err.to_string()))?;
+
+    Ok(shard_key)
 }
 
-const CHECKSUM_ALGORITHM: Code = Code::Blake2b256;
+// Identifies how the ChaCha key protecting an [`EncryptedKeyShard`] was
+// obtained, so `decrypt`/`decrypt_with_passphrase` can tell the caller
+// they're using the wrong recovery method instead of just failing AEAD
+// verification.
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum KeySource {
+    /// The key was generated at random and is recovered from a 24-word
+    /// BIP-39 mnemonic (see [`KeyShardCodewords`]).
+    Codewords,
+    /// The key was derived from a user-chosen passphrase via Argon2id.
+    Passphrase { salt: Argon2Salt, params: Argon2Params },
+}
+
+#[cfg(test)]
+impl quickcheck::Arbitrary for KeySource {
+    fn arbitrary(g: &mut quickcheck::Gen) -> Self {
+        if bool::arbitrary(g) {
+            Self::Codewords
+        } else {
+            let mut salt = Argon2Salt::default();
+            arbitrary_fill_slice(g, &mut salt);
+            Self::Passphrase {
+                salt,
+                params: Argon2Params::arbitrary(g),
+            }
+        }
+    }
+}
+
+// Algorithm used to checksum newly-created documents/shards. This is only
+// the *default* for things we mint ourselves -- verification always honours
+// whatever code is embedded in the `Multihash` being checked, so that future
+// documents can migrate to a different algorithm without breaking the wire
+// format. Only a fixed allow-list of codes is ever accepted on the
+// verification side, so a malicious or corrupted document can't coerce a
+// reader into trusting a weak or unimplemented hash.
+const DEFAULT_CHECKSUM_ALGORITHM: Code = Code::Blake2b256;
+const ALLOWED_CHECKSUM_ALGORITHMS: &[Code] = &[Code::Blake2b256, Code::Blake2b512, Code::Sha2_256];
+
+fn is_allowed_checksum_algorithm(code: Code) -> bool {
+    ALLOWED_CHECKSUM_ALGORITHMS.contains(&code)
+}
 
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
     #[error("security invariant violated: {}", .0)]
-    InvariantViolation(&'static str),
+    InvariantViolation(String),
 
     #[error("missing necessary cabibilities to complete request: {}", .0)]
     MissingCapability(&'static str),
@@ -85,12 +232,88 @@ impl From<anyhow::Error> for Error {
     }
 }
 
+// Identity key algorithm used to sign documents/shards. This is only the
+// *default* for identities we mint ourselves -- verification always honours
+// whichever algorithm an `Identity` was actually tagged with on the wire, so
+// that future documents can migrate to a different key type without
+// breaking the wire format. Only a fixed allow-list of algorithms is ever
+// accepted on the verification side, mirroring how checksum algorithm
+// agility is handled above.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum IdentityKeyAlgorithm {
+    Ed25519,
+}
+
+impl IdentityKeyAlgorithm {
+    fn tag(self) -> u32 {
+        match self {
+            Self::Ed25519 => PREFIX_ED25519_PUB,
+        }
+    }
+
+    fn from_tag(tag: u32) -> Option<Self> {
+        match tag {
+            PREFIX_ED25519_PUB => Some(Self::Ed25519),
+            _ => None,
+        }
+    }
+}
+
+const DEFAULT_IDENTITY_KEY_ALGORITHM: IdentityKeyAlgorithm = IdentityKeyAlgorithm::Ed25519;
+const ALLOWED_IDENTITY_KEY_ALGORITHMS: &[IdentityKeyAlgorithm] = &[IdentityKeyAlgorithm::Ed25519];
+
+fn is_allowed_identity_key_algorithm(algorithm: IdentityKeyAlgorithm) -> bool {
+    ALLOWED_IDENTITY_KEY_ALGORITHMS.contains(&algorithm)
+}
+
 #[derive(Clone, Debug, Eq, PartialEq)]
 struct Identity {
+    algorithm: IdentityKeyAlgorithm,
     id_public_key: PublicKey,
     id_signature: Signature,
 }
 
+impl Identity {
+    /// Verifies `id_signature` over `signable_bytes`, failing closed if
+    /// `algorithm` isn't in [`ALLOWED_IDENTITY_KEY_ALGORITHMS`] rather than
+    /// assuming Ed25519. There's only one algorithm implemented today, but
+    /// the dispatch exists so a reader can reject a document/shard that
+    /// selects an algorithm it doesn't (yet) know how to verify, instead of
+    /// silently mis-verifying it.
+    fn verify(&self, signable_bytes: &[u8]) -> Result<(), Error> {
+        if !is_allowed_identity_key_algorithm(self.algorithm) {
+            return Err(Error::InvariantViolation(
+                "identity uses a forbidden key algorithm".to_string(),
+            ));
+        }
+
+        match self.algorithm {
+            IdentityKeyAlgorithm::Ed25519 => self
+                .id_public_key
+                .verify(signable_bytes, &self.id_signature)
+                .map_err(|_| {
+                    Error::InvariantViolation("identity signature does not match".to_string())
+                }),
+        }
+    }
+}
+
+// Tags `id_public_key` with `algorithm`'s wire prefix before appending it, so
+// the encoding is self-describing rather than a bare blob of key material.
+// Centralising the tagging here means a future key type only needs to
+// change [`IdentityKeyAlgorithm`], rather than every caller that signs
+// something.
+fn append_tagged_public_key(
+    bytes: &mut Vec<u8>,
+    algorithm: IdentityKeyAlgorithm,
+    id_public_key: &PublicKey,
+) {
+    varuint_encode::u32(algorithm.tag(), &mut varuint_encode::u32_buffer())
+        .iter()
+        .chain(id_public_key.as_bytes())
+        .for_each(|b| bytes.push(*b));
+}
+
 #[cfg(test)]
 impl quickcheck::Arbitrary for Identity {
     fn arbitrary(g: &mut quickcheck::Gen) -> Self {
@@ -100,6 +323,7 @@ impl quickcheck::Arbitrary for Identity {
         let id_signature = id_keypair.sign(&bytes);
 
         Self {
+            algorithm: DEFAULT_IDENTITY_KEY_ALGORITHM,
             id_public_key: id_keypair.public,
             id_signature,
         }
@@ -133,11 +357,8 @@ impl KeyShardBuilder {
     fn signable_bytes(&self, id_public_key: &PublicKey) -> Vec<u8> {
         let mut bytes = self.to_wire();
 
-        // Append the Ed25519 public key used for signing.
-        varuint_encode::u32(PREFIX_ED25519_PUB, &mut varuint_encode::u32_buffer())
-            .iter()
-            .chain(id_public_key.as_bytes())
-            .for_each(|b| bytes.push(*b));
+        // Append the public key used for signing.
+        append_tagged_public_key(&mut bytes, DEFAULT_IDENTITY_KEY_ALGORITHM, id_public_key);
         bytes
     }
 
@@ -146,6 +367,7 @@ impl KeyShardBuilder {
         KeyShard {
             inner: self,
             identity: Identity {
+                algorithm: DEFAULT_IDENTITY_KEY_ALGORITHM,
                 id_public_key: id_keypair.public,
                 id_signature: id_keypair.sign(&bytes),
             },
@@ -159,7 +381,7 @@ impl quickcheck::Arbitrary for KeyShardBuilder {
         let bytes = Vec::<u8>::arbitrary(g);
         Self {
             version: 0,
-            doc_chksum: CHECKSUM_ALGORITHM.digest(&bytes[..]),
+            doc_chksum: DEFAULT_CHECKSUM_ALGORITHM.digest(&bytes[..]),
             shard: Shard::arbitrary(g),
         }
     }
@@ -194,14 +416,16 @@ impl KeyShard {
         // Serialise.
         let wire_shard = self.to_wire();
 
-        // Generate key and nonce.
+        // Generate key and nonce. We always use the wider XChaCha20-Poly1305
+        // nonce for newly-generated shards, since it's large enough to be
+        // drawn at random without meaningful collision risk.
         let mut shard_key = ChaChaPolyKey::default();
         rand::thread_rng().fill_bytes(&mut shard_key);
-        let mut shard_nonce = ChaChaPolyNonce::default();
+        let mut shard_nonce = XChaChaPolyNonce::default();
         rand::thread_rng().fill_bytes(&mut shard_nonce);
 
         // Encrypt the contents.
-        let aead = ChaCha20Poly1305::new(&shard_key);
+        let aead = XChaCha20Poly1305::new(&shard_key);
         let wire_shard = aead
             .encrypt(&shard_nonce, wire_shard.as_slice())
             .map_err(Error::AeadEncryption)?;
@@ -221,23 +445,69 @@ impl KeyShard {
 
         // Create wrapper shard.
         let shard = EncryptedKeyShard {
-            nonce: shard_nonce,
+            key_source: KeySource::Codewords,
+            nonce: DocumentNonce::XChaCha20Poly1305(shard_nonce),
             ciphertext: wire_shard,
         };
 
         Ok((shard, codewords))
     }
+
+    /// Like [`KeyShard::encrypt`], but the ChaCha key is derived from a
+    /// user-chosen passphrase via Argon2id instead of being generated at
+    /// random and handed back as BIP-39 codewords. Useful for users who'd
+    /// rather memorise/write down a passphrase of their own choosing.
+    pub fn encrypt_with_passphrase(self, passphrase: &str) -> Result<EncryptedKeyShard, Error> {
+        // Serialise.
+        let wire_shard = self.to_wire();
+
+        // Derive the key from the passphrase via Argon2id.
+        let mut salt = Argon2Salt::default();
+        rand::thread_rng().fill_bytes(&mut salt);
+        let params = Argon2Params::default();
+        let shard_key = derive_argon2_key(passphrase, &salt, &params)?;
+
+        // Generate nonce and encrypt the contents.
+        let mut shard_nonce = XChaChaPolyNonce::default();
+        rand::thread_rng().fill_bytes(&mut shard_nonce);
+        let aead = XChaCha20Poly1305::new(&shard_key);
+        let ciphertext = aead
+            .encrypt(&shard_nonce, wire_shard.as_slice())
+            .map_err(Error::AeadEncryption)?;
+
+        Ok(EncryptedKeyShard {
+            key_source: KeySource::Passphrase { salt, params },
+            nonce: DocumentNonce::XChaCha20Poly1305(shard_nonce),
+            ciphertext,
+        })
+    }
 }
 
 #[derive(Clone, Debug)]
 #[cfg_attr(test, derive(PartialEq, Eq))]
 pub struct EncryptedKeyShard {
-    nonce: ChaChaPolyNonce,
+    key_source: KeySource,
+    nonce: DocumentNonce,
     ciphertext: Vec<u8>,
 }
 
 impl EncryptedKeyShard {
+    fn open(&self, shard_key: &ChaChaPolyKey) -> Result<Vec<u8>, aead::Error> {
+        match &self.nonce {
+            DocumentNonce::ChaCha20Poly1305(nonce) => {
+                ChaCha20Poly1305::new(shard_key).decrypt(nonce, self.ciphertext.as_slice())
+            }
+            DocumentNonce::XChaCha20Poly1305(nonce) => {
+                XChaCha20Poly1305::new(shard_key).decrypt(nonce, self.ciphertext.as_slice())
+            }
+        }
+    }
+
     pub fn decrypt(self, codewords: &KeyShardCodewords) -> Result<KeyShard, String> {
+        if !matches!(self.key_source, KeySource::Codewords) {
+            return Err("shard was encrypted with a passphrase, not codewords".to_string());
+        }
+
         // Convert BIP-39 mnemonic to a key.
         let phrase = codewords[..].join(" ").to_lowercase();
         let mnemonic =
@@ -246,11 +516,28 @@ impl EncryptedKeyShard {
         let mut shard_key = ChaChaPolyKey::default();
         shard_key.copy_from_slice(mnemonic.entropy());
 
-        // Decrypt the contents.
-        let aead = ChaCha20Poly1305::new(&shard_key);
-        let wire_shard = aead
-            .decrypt(&self.nonce, self.ciphertext.as_slice())
-            .map_err(|err| format!("{:?}", err))?; // XXX: Ugly, fix this.
+        // Decrypt the contents, picking the cipher based on which nonce
+        // variant was actually stored in the wire shard.
+        let wire_shard = self.open(&shard_key).map_err(|err| format!("{:?}", err))?; // XXX: Ugly, fix this.
+
+        // Deserialise.
+        KeyShard::from_wire(wire_shard)
+    }
+
+    /// Counterpart to [`KeyShard::encrypt_with_passphrase`]: re-derives the
+    /// ChaCha key from the passphrase using the salt and Argon2 parameters
+    /// embedded in the shard.
+    pub fn decrypt_with_passphrase(self, passphrase: &str) -> Result<KeyShard, String> {
+        let (salt, params) = match &self.key_source {
+            KeySource::Passphrase { salt, params } => (salt, params),
+            KeySource::Codewords => {
+                return Err("shard was encrypted with codewords, not a passphrase".to_string())
+            }
+        };
+
+        let shard_key =
+            derive_argon2_key(passphrase, salt, params).map_err(|err| err.to_string())?;
+        let wire_shard = self.open(&shard_key).map_err(|err| format!("{:?}", err))?; // XXX: Ugly, fix this.
 
         // Deserialise.
         KeyShard::from_wire(wire_shard)
@@ -260,10 +547,21 @@ impl EncryptedKeyShard {
 #[cfg(test)]
 impl quickcheck::Arbitrary for EncryptedKeyShard {
     fn arbitrary(g: &mut quickcheck::Gen) -> Self {
-        let mut nonce = ChaChaPolyNonce::default();
-        arbitrary_fill_slice(g, &mut nonce);
+        let nonce = if bool::arbitrary(g) {
+            let mut nonce = ChaChaPolyNonce::default();
+            arbitrary_fill_slice(g, &mut nonce);
+            DocumentNonce::ChaCha20Poly1305(nonce)
+        } else {
+            let mut nonce = XChaChaPolyNonce::default();
+            arbitrary_fill_slice(g, &mut nonce);
+            DocumentNonce::XChaCha20Poly1305(nonce)
+        };
         let ciphertext = Vec::<u8>::arbitrary(g);
-        Self { nonce, ciphertext }
+        Self {
+            key_source: KeySource::arbitrary(g),
+            nonce,
+            ciphertext,
+        }
     }
 }
 
@@ -271,6 +569,12 @@ impl quickcheck::Arbitrary for EncryptedKeyShard {
 struct MainDocumentMeta {
     version: u32, // must be 0 for this version
     quorum_size: u32,
+    // Hardened SLIP-0010 derivation index used to derive this document's
+    // identity keypair from a master seed, if the backup was created that
+    // way. Recorded so the identity keypair can be unambiguously
+    // re-derived later; `None` means the keypair was generated
+    // independently, as it always used to be.
+    identity_derivation_index: Option<u32>,
 }
 
 impl MainDocumentMeta {
@@ -278,9 +582,7 @@ impl MainDocumentMeta {
         let mut bytes = self.to_wire();
 
         // Append the public key used for signing.
-        // XXX: Make this much nicer...
-        bytes.push(b'k');
-        id_public_key.as_bytes().iter().for_each(|b| bytes.push(*b));
+        append_tagged_public_key(&mut bytes, DEFAULT_IDENTITY_KEY_ALGORITHM, id_public_key);
 
         bytes
     }
@@ -292,6 +594,7 @@ impl quickcheck::Arbitrary for MainDocumentMeta {
         Self {
             version: 0,
             quorum_size: u32::arbitrary(g),
+            identity_derivation_index: Option::<u32>::arbitrary(g),
         }
     }
 }
@@ -299,7 +602,7 @@ impl quickcheck::Arbitrary for MainDocumentMeta {
 #[derive(Clone, Debug, Eq, PartialEq)]
 struct MainDocumentBuilder {
     meta: MainDocumentMeta,
-    nonce: ChaChaPolyNonce,
+    nonce: DocumentNonce,
     ciphertext: Vec<u8>,
 }
 
@@ -307,11 +610,8 @@ impl MainDocumentBuilder {
     fn signable_bytes(&self, id_public_key: &PublicKey) -> Vec<u8> {
         let mut bytes = self.to_wire();
 
-        // Append the Ed25519 public key used for signing.
-        varuint_encode::u32(PREFIX_ED25519_PUB, &mut varuint_encode::u32_buffer())
-            .iter()
-            .chain(id_public_key.as_bytes())
-            .for_each(|b| bytes.push(*b));
+        // Append the public key used for signing.
+        append_tagged_public_key(&mut bytes, DEFAULT_IDENTITY_KEY_ALGORITHM, id_public_key);
         bytes
     }
 
@@ -320,6 +620,7 @@ impl MainDocumentBuilder {
         MainDocument {
             inner: self,
             identity: Identity {
+                algorithm: DEFAULT_IDENTITY_KEY_ALGORITHM,
                 id_public_key: id_keypair.public,
                 id_signature: id_keypair.sign(&bytes),
             },
@@ -330,8 +631,15 @@ impl MainDocumentBuilder {
 #[cfg(test)]
 impl quickcheck::Arbitrary for MainDocumentBuilder {
     fn arbitrary(g: &mut quickcheck::Gen) -> Self {
-        let mut nonce = ChaChaPolyNonce::default();
-        arbitrary_fill_slice(g, &mut nonce);
+        let nonce = if bool::arbitrary(g) {
+            let mut nonce = ChaChaPolyNonce::default();
+            arbitrary_fill_slice(g, &mut nonce);
+            DocumentNonce::ChaCha20Poly1305(nonce)
+        } else {
+            let mut nonce = XChaChaPolyNonce::default();
+            arbitrary_fill_slice(g, &mut nonce);
+            DocumentNonce::XChaCha20Poly1305(nonce)
+        };
         Self {
             meta: MainDocumentMeta::arbitrary(g),
             nonce,
@@ -351,7 +659,32 @@ impl MainDocument {
     pub const ID_LENGTH: usize = 8;
 
     pub fn checksum(&self) -> Multihash {
-        CHECKSUM_ALGORITHM.digest(&self.to_wire())
+        DEFAULT_CHECKSUM_ALGORITHM.digest(&self.to_wire())
+    }
+
+    /// Verifies that `expected` -- typically a `doc_chksum` carried by a
+    /// [`KeyShard`] -- matches this document, re-hashing with whichever
+    /// multihash code `expected` was produced with rather than assuming
+    /// [`DEFAULT_CHECKSUM_ALGORITHM`]. This lets a v0 reader validate
+    /// documents that were checksummed with a different (but still allowed)
+    /// algorithm, while still rejecting unknown or forbidden ones outright.
+    pub fn verify_checksum(&self, expected: &Multihash) -> Result<(), Error> {
+        let code = Code::try_from(expected.code()).map_err(|_| {
+            Error::InvariantViolation("checksum uses an unrecognised multihash code".to_string())
+        })?;
+        if !is_allowed_checksum_algorithm(code) {
+            return Err(Error::InvariantViolation(
+                "checksum uses a forbidden multihash code".to_string(),
+            ));
+        }
+
+        if code.digest(&self.to_wire()) != *expected {
+            return Err(Error::InvariantViolation(
+                "document checksum does not match".to_string(),
+            ));
+        }
+
+        Ok(())
     }
 
     pub fn id(&self) -> DocumentId {
@@ -389,7 +722,7 @@ pub use backup::*;
 mod test {
     use super::*;
 
-    use quickcheck::TestResult;
+    use quickcheck::{Arbitrary, TestResult};
 
     // NOTE: We use u16s and u8s here (and limit the range) because generating
     //       ridiculously large dealers takes too long because of the amount of
@@ -447,5 +780,139 @@ mod test {
         assert_eq!(shard, shard2);
     }
 
+    #[test]
+    fn key_shard_legacy_chacha20poly1305_roundtrip() {
+        // `key_shard_encryption_roundtrip` above always takes the
+        // XChaCha20-Poly1305 path that `KeyShard::encrypt` actually uses, so
+        // it never exercises the legacy 12-byte-nonce branch that
+        // `EncryptedKeyShard::open` keeps around purely so shards sealed
+        // before XChaCha20-Poly1305 was introduced can still be decrypted.
+        // Seal a shard with ChaCha20Poly1305 directly, the way an old shard
+        // would actually have been produced, and confirm it still opens.
+        let shard = KeyShard::arbitrary(&mut quickcheck::Gen::new(16));
+        let wire_shard = shard.to_wire();
+
+        let mut shard_key = ChaChaPolyKey::default();
+        rand::thread_rng().fill_bytes(&mut shard_key);
+        let mut shard_nonce = ChaChaPolyNonce::default();
+        rand::thread_rng().fill_bytes(&mut shard_nonce);
+
+        let ciphertext = ChaCha20Poly1305::new(&shard_key)
+            .encrypt(&shard_nonce, wire_shard.as_slice())
+            .unwrap();
+
+        let phrase = Mnemonic::from_entropy(&shard_key, CODEWORD_LANGUAGE)
+            .unwrap()
+            .into_phrase();
+        let mut codewords = KeyShardCodewords::default();
+        codewords.clone_from_slice(
+            phrase
+                .split_whitespace()
+                .map(|s| s.to_owned())
+                .collect::<Vec<_>>()
+                .as_slice(),
+        );
+
+        let enc_shard = EncryptedKeyShard {
+            key_source: KeySource::Codewords,
+            nonce: DocumentNonce::ChaCha20Poly1305(shard_nonce),
+            ciphertext,
+        };
+
+        let shard2 = enc_shard.decrypt(&codewords).unwrap();
+        assert_eq!(shard, shard2);
+    }
+
+    #[quickcheck]
+    fn key_shard_passphrase_encryption_roundtrip(shard: KeyShard, passphrase: String) -> TestResult {
+        if passphrase.is_empty() {
+            return TestResult::discard();
+        }
+
+        let enc_shard = shard.clone().encrypt_with_passphrase(&passphrase).unwrap();
+        let shard2 = enc_shard.decrypt_with_passphrase(&passphrase).unwrap();
+
+        TestResult::from_bool(shard == shard2)
+    }
+
+    #[test]
+    fn decrypt_with_passphrase_rejects_oversized_argon2_params() {
+        let shard = KeyShard::arbitrary(&mut quickcheck::Gen::new(16));
+        let passphrase = "a passphrase";
+
+        let mut enc_shard = shard.encrypt_with_passphrase(passphrase).unwrap();
+        // A corrupted/malicious shard could claim any memory_cost_kib it
+        // likes -- this must be rejected before it's handed to Argon2,
+        // rather than trying to honour it.
+        match &mut enc_shard.key_source {
+            KeySource::Passphrase { params, .. } => {
+                params.memory_cost_kib = u32::MAX;
+            }
+            KeySource::Codewords => unreachable!(),
+        }
+
+        assert!(enc_shard.decrypt_with_passphrase(passphrase).is_err());
+    }
+
+    #[quickcheck]
+    fn main_document_verify_checksum(main: MainDocument) {
+        // The checksum dispatches on whatever code `expected` carries, so
+        // this must hold regardless of which allowed algorithm was used to
+        // produce it.
+        for &code in ALLOWED_CHECKSUM_ALGORITHMS {
+            let chksum = code.digest(&main.to_wire());
+            main.verify_checksum(&chksum).unwrap();
+        }
+
+        // A forbidden algorithm must be rejected outright, even though the
+        // digest bytes would otherwise match.
+        let forbidden = Code::Sha1.digest(&main.to_wire());
+        assert!(main.verify_checksum(&forbidden).is_err());
+
+        // A mismatched digest under an allowed algorithm must also fail.
+        let mismatched = DEFAULT_CHECKSUM_ALGORITHM.digest(b"not the document");
+        assert!(main.verify_checksum(&mismatched).is_err());
+    }
+
+    #[test]
+    fn identity_key_algorithm_tag_roundtrip() {
+        for &algorithm in ALLOWED_IDENTITY_KEY_ALGORITHMS {
+            assert_eq!(
+                IdentityKeyAlgorithm::from_tag(algorithm.tag()),
+                Some(algorithm)
+            );
+        }
+
+        // An unrecognised tag must not be coerced into an allowed algorithm.
+        assert_eq!(IdentityKeyAlgorithm::from_tag(0xdead_beef), None);
+    }
+
+    #[quickcheck]
+    fn identity_verify(identity: Identity, bytes: Vec<u8>) -> TestResult {
+        if identity
+            .id_public_key
+            .verify(&bytes, &identity.id_signature)
+            .is_ok()
+        {
+            return TestResult::from_bool(identity.verify(&bytes).is_ok());
+        }
+
+        TestResult::from_bool(identity.verify(&bytes).is_err())
+    }
+
+    #[test]
+    fn identity_verify_accepts_genuine_signature() {
+        let id_keypair = Keypair::generate(&mut rand::thread_rng());
+        let bytes = b"some signable bytes".to_vec();
+
+        let identity = Identity {
+            algorithm: DEFAULT_IDENTITY_KEY_ALGORITHM,
+            id_public_key: id_keypair.public,
+            id_signature: id_keypair.sign(&bytes),
+        };
+        assert!(identity.verify(&bytes).is_ok());
+        assert!(identity.verify(b"different bytes").is_err());
+    }
+
     // TODO: Add many more tests...
 }
@@ -0,0 +1,234 @@
+/*
+ * paperback: paper backup generator suitable for long-term storage
+ * Copyright (C) 2018-2020 Aleksa Sarai <cyphar@cyphar.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use crate::v0::{
+    wire::ToWire, Error, KeyShard, MainDocument, ShardId,
+};
+
+use std::collections::BTreeMap;
+
+/// A quorum of [`KeyShard`]s collected from the outside world, alongside the
+/// [`MainDocument`] they protect. Nothing about the shards pushed here has
+/// been checked yet -- they may be duplicates, forgeries, or simply not add
+/// up to a quorum -- which is why `validate()` has to be called before the
+/// document can be recovered.
+#[derive(Clone, Debug, Default)]
+pub struct UntrustedQuorum {
+    main_document: Option<MainDocument>,
+    shards: Vec<KeyShard>,
+}
+
+impl UntrustedQuorum {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn main_document(&mut self, main_document: MainDocument) -> &mut Self {
+        self.main_document = Some(main_document);
+        self
+    }
+
+    pub fn push_shard(&mut self, shard: KeyShard) -> &mut Self {
+        self.shards.push(shard);
+        self
+    }
+
+    /// Checks that the pushed shards actually form a quorum: shards that
+    /// collide on their Shamir x-coordinate (`KeyShard::id()`) must either be
+    /// byte-identical duplicates (which are silently de-duplicated) or the
+    /// operator has been handed conflicting shards for the same physical
+    /// sheet, which is a security invariant violation. Once the shards have
+    /// been de-duplicated, there must be at least `quorum_size` *distinct*
+    /// x-coordinates left -- otherwise a caller could be tricked into
+    /// thinking a quorum was met by feeding in copies of the same shard.
+    pub fn validate(self) -> Result<Quorum, Error> {
+        let main_document = self
+            .main_document
+            .ok_or(Error::MissingCapability("no main document in quorum"))?;
+
+        let mut by_id: BTreeMap<ShardId, KeyShard> = BTreeMap::new();
+        let mut conflicting_ids: Vec<ShardId> = vec![];
+
+        for shard in self.shards {
+            match by_id.get(&shard.id()) {
+                None => {
+                    by_id.insert(shard.id(), shard);
+                }
+                Some(existing) if existing.to_wire() == shard.to_wire() => {
+                    // Byte-identical duplicate -- ignore the repeat rather
+                    // than letting it count towards the quorum twice.
+                }
+                Some(_) => conflicting_ids.push(shard.id()),
+            }
+        }
+
+        if !conflicting_ids.is_empty() {
+            conflicting_ids.sort();
+            conflicting_ids.dedup();
+            return Err(Error::InvariantViolation(format!(
+                "shards with conflicting contents share the same id: {}",
+                conflicting_ids.join(", ")
+            )));
+        }
+
+        let quorum_size = main_document.quorum_size() as usize;
+        // A document can't require fewer than one shard to recover -- a
+        // `quorum_size` of 0 would otherwise let an empty (or corrupted)
+        // `UntrustedQuorum` sail through `by_id.len() < quorum_size` without
+        // a single contributory shard, which defeats the whole point of
+        // this check.
+        if quorum_size < 1 || by_id.len() < quorum_size {
+            return Err(Error::InvariantViolation(
+                "not enough distinct shards were provided to make up a quorum".to_string(),
+            ));
+        }
+
+        Ok(Quorum {
+            main_document,
+            shards: by_id.into_values().collect(),
+        })
+    }
+}
+
+/// A set of shards that has passed [`UntrustedQuorum::validate`] -- distinct,
+/// contributory, and (once signature verification of the individual shards
+/// and the main document has also been completed) trustworthy enough to
+/// attempt document recovery with.
+#[derive(Clone, Debug)]
+pub struct Quorum {
+    main_document: MainDocument,
+    shards: Vec<KeyShard>,
+}
+
+impl Quorum {
+    pub fn main_document(&self) -> &MainDocument {
+        &self.main_document
+    }
+
+    pub fn shards(&self) -> &[KeyShard] {
+        &self.shards
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::v0::{
+        DocumentNonce, KeyShardBuilder, MainDocumentBuilder, MainDocumentMeta, XChaChaPolyNonce,
+        DEFAULT_CHECKSUM_ALGORITHM,
+    };
+
+    use ed25519_dalek::Keypair;
+    use multihash::MultihashDigest;
+    use quickcheck::{Arbitrary, Gen};
+
+    fn test_main_document(quorum_size: u32) -> MainDocument {
+        let builder = MainDocumentBuilder {
+            meta: MainDocumentMeta {
+                version: 0,
+                quorum_size,
+                identity_derivation_index: None,
+            },
+            nonce: DocumentNonce::XChaCha20Poly1305(XChaChaPolyNonce::default()),
+            ciphertext: vec![1, 2, 3],
+        };
+
+        let id_keypair = Keypair::generate(&mut rand::thread_rng());
+        builder.sign(&id_keypair)
+    }
+
+    fn test_shard() -> KeyShard {
+        let mut gen = Gen::new(16);
+        let id_keypair = Keypair::generate(&mut rand::thread_rng());
+        KeyShardBuilder::arbitrary(&mut gen).sign(&id_keypair)
+    }
+
+    // A shard that shares `shard`'s id (the underlying Shamir shard is
+    // untouched) but whose contents otherwise differ, as if two different
+    // parties had forged conflicting shards for the same physical sheet.
+    fn conflicting_shard(shard: &KeyShard) -> KeyShard {
+        let mut builder = shard.inner.clone();
+        builder.doc_chksum = DEFAULT_CHECKSUM_ALGORITHM.digest(b"some other main document");
+
+        let id_keypair = Keypair::generate(&mut rand::thread_rng());
+        builder.sign(&id_keypair)
+    }
+
+    #[test]
+    fn duplicate_shards_are_merged() {
+        let main_document = test_main_document(2);
+        let shard1 = test_shard();
+        let shard2 = test_shard();
+
+        let mut quorum = UntrustedQuorum::new();
+        quorum.main_document(main_document);
+        quorum.push_shard(shard1.clone());
+        quorum.push_shard(shard1.clone()); // byte-identical duplicate
+        quorum.push_shard(shard2.clone());
+
+        let quorum = quorum.validate().unwrap();
+        assert_eq!(quorum.shards().len(), 2);
+    }
+
+    #[test]
+    fn conflicting_shards_are_rejected() {
+        let main_document = test_main_document(2);
+        let shard1 = test_shard();
+        let shard1_conflict = conflicting_shard(&shard1);
+        assert_eq!(shard1.id(), shard1_conflict.id());
+
+        let mut quorum = UntrustedQuorum::new();
+        quorum.main_document(main_document);
+        quorum.push_shard(shard1);
+        quorum.push_shard(shard1_conflict);
+
+        let err = quorum.validate().unwrap_err();
+        assert!(matches!(err, Error::InvariantViolation(_)));
+    }
+
+    #[test]
+    fn zero_quorum_size_is_rejected() {
+        // `by_id.len() < quorum_size` is never true for an empty quorum
+        // when `quorum_size` is 0, so a degenerate document must be
+        // rejected explicitly rather than relying on it.
+        let main_document = test_main_document(0);
+
+        let mut quorum = UntrustedQuorum::new();
+        quorum.main_document(main_document);
+
+        let err = quorum.validate().unwrap_err();
+        assert!(matches!(err, Error::InvariantViolation(_)));
+    }
+
+    #[test]
+    fn non_contributory_quorum_is_rejected() {
+        let main_document = test_main_document(2);
+        let shard = test_shard();
+
+        // Copies of a single shard can never make up a 2-shard quorum, no
+        // matter how many times it's pushed.
+        let mut quorum = UntrustedQuorum::new();
+        quorum.main_document(main_document);
+        quorum.push_shard(shard.clone());
+        quorum.push_shard(shard.clone());
+        quorum.push_shard(shard.clone());
+
+        let err = quorum.validate().unwrap_err();
+        assert!(matches!(err, Error::InvariantViolation(_)));
+    }
+}
@@ -0,0 +1,115 @@
+/*
+ * paperback: paper backup generator suitable for long-term storage
+ * Copyright (C) 2018-2020 Aleksa Sarai <cyphar@cyphar.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use crate::v0::{
+    wire::{FromWire, ToWire},
+    Identity, IdentityKeyAlgorithm,
+};
+
+use ed25519_dalek::{PublicKey, Signature, PUBLIC_KEY_LENGTH, SIGNATURE_LENGTH};
+use unsigned_varint::encode as varuint_encode;
+
+// Internal only -- users can't see Identity's fields.
+#[doc(hidden)]
+impl ToWire for Identity {
+    fn to_wire(&self) -> Vec<u8> {
+        let mut bytes = vec![];
+
+        // Tag the public key with its algorithm, so a reader knows how to
+        // interpret both it and the signature that follows instead of
+        // assuming Ed25519.
+        varuint_encode::u32(self.algorithm.tag(), &mut varuint_encode::u32_buffer())
+            .iter()
+            .chain(self.id_public_key.as_bytes())
+            .for_each(|b| bytes.push(*b));
+
+        bytes.extend_from_slice(&self.id_signature.to_bytes());
+        bytes
+    }
+}
+
+// Internal only -- users can't see Identity's fields.
+#[doc(hidden)]
+impl FromWire for Identity {
+    fn from_wire_partial(input: &[u8]) -> Result<(Self, &[u8]), String> {
+        use crate::v0::wire::nom_helpers;
+        use nom::{bytes::complete::take, combinator::complete, IResult};
+
+        fn parse(input: &[u8]) -> IResult<&[u8], (u32, &[u8], &[u8])> {
+            let (input, tag) = nom_helpers::u32()(input)?;
+            let (input, id_public_key) = take(PUBLIC_KEY_LENGTH)(input)?;
+            let (input, id_signature) = take(SIGNATURE_LENGTH)(input)?;
+
+            Ok((input, (tag, id_public_key, id_signature)))
+        }
+        let parse = complete(parse);
+
+        let (remain, (tag, id_public_key, id_signature)) =
+            parse(input).map_err(|err| format!("{:?}", err))?;
+
+        // Fail closed on a tag this reader doesn't recognise, rather than
+        // assuming Ed25519.
+        let algorithm = IdentityKeyAlgorithm::from_tag(tag)
+            .ok_or_else(|| format!("identity uses an unrecognised key algorithm tag: {}", tag))?;
+
+        let id_public_key =
+            PublicKey::from_bytes(id_public_key).map_err(|err| format!("{:?}", err))?;
+        let id_signature =
+            Signature::from_bytes(id_signature).map_err(|err| format!("{:?}", err))?;
+
+        Ok((
+            Identity {
+                algorithm,
+                id_public_key,
+                id_signature,
+            },
+            remain,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[quickcheck]
+    fn identity_roundtrip(identity: Identity) {
+        let identity2 = Identity::from_wire(identity.to_wire()).unwrap();
+        assert_eq!(identity, identity2);
+    }
+
+    #[test]
+    fn identity_from_wire_rejects_forged_algorithm_tag() {
+        use ed25519_dalek::{Keypair, Signer};
+
+        let id_keypair = Keypair::generate(&mut rand::thread_rng());
+        let id_signature = id_keypair.sign(b"some signable bytes");
+
+        // A tag that doesn't name any algorithm this reader knows about --
+        // built directly rather than corrupting a real encoding, since the
+        // real prefix value isn't known outside `wire::prefixes`.
+        let mut wire = vec![];
+        varuint_encode::u32(u32::MAX, &mut varuint_encode::u32_buffer())
+            .iter()
+            .chain(id_keypair.public.as_bytes())
+            .for_each(|b| wire.push(*b));
+        wire.extend_from_slice(&id_signature.to_bytes());
+
+        assert!(Identity::from_wire(wire).is_err());
+    }
+}
@@ -0,0 +1,171 @@
+/*
+ * paperback: paper backup generator suitable for long-term storage
+ * Copyright (C) 2018-2020 Aleksa Sarai <cyphar@cyphar.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use crate::v0::{
+    wire::{prefixes::*, FromWire, ToWire},
+    Argon2Params, Argon2Salt, ChaChaPolyNonce, DocumentNonce, EncryptedKeyShard, KeySource,
+    XChaChaPolyNonce, ARGON2_SALT_LENGTH, CHACHAPOLY_NONCE_LENGTH, XCHACHAPOLY_NONCE_LENGTH,
+};
+
+use unsigned_varint::encode;
+
+// Internal only -- users can't see EncryptedKeyShard's fields.
+#[doc(hidden)]
+impl ToWire for EncryptedKeyShard {
+    fn to_wire(&self) -> Vec<u8> {
+        let mut buffer = encode::u64_buffer();
+        let mut bytes = vec![];
+
+        // Encode the key source, if it isn't the legacy default (a random
+        // key handed back as BIP-39 codewords) -- old shards never had this
+        // prefix, and are implicitly `KeySource::Codewords`.
+        if let KeySource::Passphrase { salt, params } = &self.key_source {
+            encode::u64(PREFIX_ARGON2ID_KEY_SOURCE, &mut buffer)
+                .iter()
+                .for_each(|b| bytes.push(*b));
+            bytes.extend_from_slice(salt);
+
+            let mut u32_buffer = encode::u32_buffer();
+            encode::u32(params.memory_cost_kib, &mut u32_buffer)
+                .iter()
+                .for_each(|b| bytes.push(*b));
+            encode::u32(params.time_cost, &mut u32_buffer)
+                .iter()
+                .for_each(|b| bytes.push(*b));
+            encode::u32(params.parallelism, &mut u32_buffer)
+                .iter()
+                .for_each(|b| bytes.push(*b));
+        }
+
+        // Encode nonce. The prefix identifies which cipher was used to seal
+        // the ciphertext, so old (12-byte) and new (24-byte) nonces can be
+        // told apart on read.
+        match &self.nonce {
+            DocumentNonce::ChaCha20Poly1305(nonce) => {
+                encode::u64(PREFIX_CHACHA20POLY1305_NONCE, &mut buffer)
+                    .iter()
+                    .chain(nonce)
+                    .for_each(|b| bytes.push(*b));
+            }
+            DocumentNonce::XChaCha20Poly1305(nonce) => {
+                encode::u64(PREFIX_XCHACHA20POLY1305_NONCE, &mut buffer)
+                    .iter()
+                    .chain(nonce)
+                    .for_each(|b| bytes.push(*b));
+            }
+        }
+
+        // Encode ciphertext.
+        encode::u64(PREFIX_CHACHA20POLY1305_CIPHERTEXT, &mut buffer)
+            .iter()
+            .chain(encode::usize(
+                self.ciphertext.len(),
+                &mut encode::usize_buffer(),
+            ))
+            .chain(&self.ciphertext)
+            .for_each(|b| bytes.push(*b));
+
+        bytes
+    }
+}
+
+// Internal only -- users can't see EncryptedKeyShard's fields.
+#[doc(hidden)]
+impl FromWire for EncryptedKeyShard {
+    fn from_wire_partial(input: &[u8]) -> Result<(Self, &[u8]), String> {
+        use crate::v0::wire::nom_helpers;
+        use nom::{
+            branch::alt,
+            bytes::complete::take,
+            combinator::{complete, opt},
+            IResult,
+        };
+
+        fn argon2id_key_source(input: &[u8]) -> IResult<&[u8], KeySource> {
+            let (input, _) = nom_helpers::u64_tag(PREFIX_ARGON2ID_KEY_SOURCE)(input)?;
+            let (input, salt) = take(ARGON2_SALT_LENGTH)(input)?;
+            let (input, memory_cost_kib) = nom_helpers::u32()(input)?;
+            let (input, time_cost) = nom_helpers::u32()(input)?;
+            let (input, parallelism) = nom_helpers::u32()(input)?;
+
+            let mut salt_buffer = Argon2Salt::default();
+            salt_buffer.copy_from_slice(salt);
+
+            let key_source = KeySource::Passphrase {
+                salt: salt_buffer,
+                params: Argon2Params {
+                    memory_cost_kib,
+                    time_cost,
+                    parallelism,
+                },
+            };
+
+            Ok((input, key_source))
+        }
+
+        fn chacha20poly1305_nonce(input: &[u8]) -> IResult<&[u8], DocumentNonce> {
+            let (input, _) = nom_helpers::u64_tag(PREFIX_CHACHA20POLY1305_NONCE)(input)?;
+            let (input, nonce) = take(CHACHAPOLY_NONCE_LENGTH)(input)?;
+
+            let mut buffer = ChaChaPolyNonce::default();
+            buffer.copy_from_slice(nonce);
+
+            Ok((input, DocumentNonce::ChaCha20Poly1305(buffer)))
+        }
+
+        fn xchacha20poly1305_nonce(input: &[u8]) -> IResult<&[u8], DocumentNonce> {
+            let (input, _) = nom_helpers::u64_tag(PREFIX_XCHACHA20POLY1305_NONCE)(input)?;
+            let (input, nonce) = take(XCHACHAPOLY_NONCE_LENGTH)(input)?;
+
+            let mut buffer = XChaChaPolyNonce::default();
+            buffer.copy_from_slice(nonce);
+
+            Ok((input, DocumentNonce::XChaCha20Poly1305(buffer)))
+        }
+
+        fn parse(input: &[u8]) -> IResult<&[u8], (KeySource, DocumentNonce, &[u8])> {
+            // Absent unless the shard was encrypted with a passphrase, so
+            // old shards (implicitly `KeySource::Codewords`) still parse.
+            let (input, key_source) = opt(argon2id_key_source)(input)?;
+            let key_source = key_source.unwrap_or(KeySource::Codewords);
+
+            // Either nonce prefix is accepted on read, so shards sealed
+            // before XChaCha20-Poly1305 was introduced still decrypt.
+            let (input, nonce) = alt((chacha20poly1305_nonce, xchacha20poly1305_nonce))(input)?;
+
+            let (input, _) = nom_helpers::u64_tag(PREFIX_CHACHA20POLY1305_CIPHERTEXT)(input)?;
+            let (input, length) = nom_helpers::usize()(input)?;
+            let (input, ciphertext) = take(length)(input)?;
+
+            Ok((input, (key_source, nonce, ciphertext)))
+        }
+        let parse = complete(parse);
+
+        let (remain, (key_source, nonce, ciphertext)) =
+            parse(input).map_err(|err| format!("{:?}", err))?;
+
+        Ok((
+            EncryptedKeyShard {
+                key_source,
+                nonce,
+                ciphertext: ciphertext.into(),
+            },
+            remain,
+        ))
+    }
+}
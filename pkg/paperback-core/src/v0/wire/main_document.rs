@@ -18,8 +18,8 @@
 
 use crate::v0::{
     wire::{prefixes::*, FromWire, ToWire},
-    ChaChaPolyNonce, Identity, MainDocument, MainDocumentBuilder, MainDocumentMeta,
-    CHACHAPOLY_NONCE_LENGTH,
+    ChaChaPolyNonce, DocumentNonce, Identity, MainDocument, MainDocumentBuilder, MainDocumentMeta,
+    XChaChaPolyNonce, CHACHAPOLY_NONCE_LENGTH, XCHACHAPOLY_NONCE_LENGTH,
 };
 
 use unsigned_varint::encode;
@@ -41,6 +41,19 @@ impl ToWire for MainDocumentMeta {
             .iter()
             .for_each(|b| bytes.push(*b));
 
+        // Encode the HD derivation index, if the identity keypair was
+        // derived from a master seed. Gated behind its own prefix (rather
+        // than always present) so documents that predate HD derivation
+        // still parse.
+        if let Some(index) = self.identity_derivation_index {
+            encode::u64(PREFIX_IDENTITY_DERIVATION_INDEX, &mut encode::u64_buffer())
+                .iter()
+                .for_each(|b| bytes.push(*b));
+            encode::u32(index, &mut buffer)
+                .iter()
+                .for_each(|b| bytes.push(*b));
+        }
+
         bytes
     }
 }
@@ -50,15 +63,24 @@ impl ToWire for MainDocumentMeta {
 impl FromWire for MainDocumentMeta {
     fn from_wire_partial(input: &[u8]) -> Result<(Self, &[u8]), String> {
         use crate::v0::wire::nom_helpers;
-        use nom::{combinator::complete, IResult};
+        use nom::{
+            combinator::{complete, opt},
+            sequence::preceded,
+            IResult,
+        };
 
         fn parse(input: &[u8]) -> IResult<&[u8], MainDocumentMeta> {
             let (input, version) = nom_helpers::u32()(input)?;
             let (input, quorum_size) = nom_helpers::u32()(input)?;
+            let (input, identity_derivation_index) = opt(preceded(
+                nom_helpers::u64_tag(PREFIX_IDENTITY_DERIVATION_INDEX),
+                nom_helpers::u32(),
+            ))(input)?;
 
             let meta = MainDocumentMeta {
                 version,
                 quorum_size,
+                identity_derivation_index,
             };
 
             Ok((input, meta))
@@ -80,11 +102,23 @@ impl ToWire for MainDocumentBuilder {
         // Encode metadata.
         bytes.append(&mut self.meta.to_wire());
 
-        // Encode nonce.
-        encode::u64(PREFIX_CHACHA20POLY1305_NONCE, &mut buffer)
-            .iter()
-            .chain(&self.nonce)
-            .for_each(|b| bytes.push(*b));
+        // Encode nonce. The prefix identifies which cipher was used to seal
+        // the ciphertext, so old (12-byte) and new (24-byte) nonces can be
+        // told apart on read.
+        match &self.nonce {
+            DocumentNonce::ChaCha20Poly1305(nonce) => {
+                encode::u64(PREFIX_CHACHA20POLY1305_NONCE, &mut buffer)
+                    .iter()
+                    .chain(nonce)
+                    .for_each(|b| bytes.push(*b));
+            }
+            DocumentNonce::XChaCha20Poly1305(nonce) => {
+                encode::u64(PREFIX_XCHACHA20POLY1305_NONCE, &mut buffer)
+                    .iter()
+                    .chain(nonce)
+                    .for_each(|b| bytes.push(*b));
+            }
+        }
 
         // Encode ciphertext.
         encode::u64(PREFIX_CHACHA20POLY1305_CIPHERTEXT, &mut buffer)
@@ -105,17 +139,32 @@ impl ToWire for MainDocumentBuilder {
 impl FromWire for MainDocumentBuilder {
     fn from_wire_partial(input: &[u8]) -> Result<(Self, &[u8]), String> {
         use crate::v0::wire::nom_helpers;
-        use nom::{bytes::complete::take, combinator::complete, IResult};
+        use nom::{branch::alt, bytes::complete::take, combinator::complete, IResult};
 
-        fn parse(input: &[u8]) -> IResult<&[u8], (ChaChaPolyNonce, &[u8])> {
+        fn chacha20poly1305_nonce(input: &[u8]) -> IResult<&[u8], DocumentNonce> {
             let (input, _) = nom_helpers::u64_tag(PREFIX_CHACHA20POLY1305_NONCE)(input)?;
             let (input, nonce) = take(CHACHAPOLY_NONCE_LENGTH)(input)?;
 
-            let nonce = {
-                let mut buffer = ChaChaPolyNonce::default();
-                buffer.copy_from_slice(nonce);
-                buffer
-            };
+            let mut buffer = ChaChaPolyNonce::default();
+            buffer.copy_from_slice(nonce);
+
+            Ok((input, DocumentNonce::ChaCha20Poly1305(buffer)))
+        }
+
+        fn xchacha20poly1305_nonce(input: &[u8]) -> IResult<&[u8], DocumentNonce> {
+            let (input, _) = nom_helpers::u64_tag(PREFIX_XCHACHA20POLY1305_NONCE)(input)?;
+            let (input, nonce) = take(XCHACHAPOLY_NONCE_LENGTH)(input)?;
+
+            let mut buffer = XChaChaPolyNonce::default();
+            buffer.copy_from_slice(nonce);
+
+            Ok((input, DocumentNonce::XChaCha20Poly1305(buffer)))
+        }
+
+        fn parse(input: &[u8]) -> IResult<&[u8], (DocumentNonce, &[u8])> {
+            // Either nonce prefix is accepted on read, so v0 documents
+            // sealed before XChaCha20-Poly1305 was introduced still decrypt.
+            let (input, nonce) = alt((chacha20poly1305_nonce, xchacha20poly1305_nonce))(input)?;
 
             let (input, _) = nom_helpers::u64_tag(PREFIX_CHACHA20POLY1305_CIPHERTEXT)(input)?;
             let (input, length) = nom_helpers::usize()(input)?;
@@ -181,4 +230,27 @@ mod test {
         let meta2 = MainDocumentMeta::from_wire(main.inner.meta.to_wire()).unwrap();
         assert_eq!(main.inner.meta, meta2);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn main_document_builder_legacy_chacha20poly1305_nonce_roundtrips() {
+        // `main_document_roundtrip` only hits the legacy ChaCha20Poly1305
+        // nonce branch when `Arbitrary` happens to flip that way, so pin it
+        // down explicitly -- old v0 documents sealed with the narrower
+        // nonce must still parse.
+        let mut nonce = ChaChaPolyNonce::default();
+        nonce.copy_from_slice(&[7u8; CHACHAPOLY_NONCE_LENGTH]);
+
+        let builder = MainDocumentBuilder {
+            meta: MainDocumentMeta {
+                version: 0,
+                quorum_size: 2,
+                identity_derivation_index: None,
+            },
+            nonce: DocumentNonce::ChaCha20Poly1305(nonce),
+            ciphertext: vec![1, 2, 3],
+        };
+
+        let builder2 = MainDocumentBuilder::from_wire(builder.to_wire()).unwrap();
+        assert_eq!(builder, builder2);
+    }
+}